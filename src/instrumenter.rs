@@ -0,0 +1,940 @@
+//! Shared instrumentation engine used by both the `instrument` CLI binary
+//! and the [`instrument_tree`] build-script entry point.
+
+use proc_macro2::Span;
+use quote::{ToTokens, format_ident};
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path as StdPath, PathBuf};
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::{
+    Block, Expr, ExprCall, ExprIf, ExprMatch, ExprWhile, ItemEnum, Pat, Path, Stmt, parse_quote,
+};
+use walkdir::WalkDir;
+
+/// Name of the manifest file written alongside the processed tree, mapping
+/// every emitted location ID back to the source it came from.
+pub const MANIFEST_FILE_NAME: &str = "sginstrument-manifest.json";
+
+/// Default cfg flag injected instrumentation calls are gated behind.
+pub const DEFAULT_CFG_NAME: &str = "fuzzing";
+
+/// One emitted instrumentation site, recorded so that a raw `(location,
+/// state_value)` pair reported by the fuzzer can be decoded back into a
+/// source file, span, and enum variant.
+#[derive(Debug, Clone)]
+struct LocationRecord {
+    location: u32,
+    state_value: u32,
+    file: PathBuf,
+    enum_name: String,
+    variant_name: String,
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+/// Derive a location ID from where the instrumentation site sits in the
+/// source, rather than from the order the visitor happened to reach it.
+/// This keeps IDs stable across runs even if traversal order changes (e.g.
+/// a different file enumeration order, or a subset of files reprocessed).
+fn location_id(file: &StdPath, span: Span, enum_name: &str, variant_name: &str) -> u32 {
+    let start = span.start();
+    let key = format!(
+        "{}:{}:{}:{enum_name}::{variant_name}",
+        file.display(),
+        start.line,
+        start.column
+    );
+
+    // FNV-1a, 32-bit.
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in key.as_bytes() {
+        hash ^= u32::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+/// Escape a string for embedding in a JSON string literal.
+fn json_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if u32::from(c) < 0x20 => escaped.push_str(&format!("\\u{:04x}", u32::from(c))),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[derive(Debug)]
+pub enum InstrumentError {
+    WrongArguments(String),
+    ErrorProcessing(PathBuf, Box<dyn Error>),
+    InvalidPath(PathBuf),
+    InvalidCfgName(String),
+}
+
+impl Display for InstrumentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongArguments(name) => {
+                write!(
+                    f,
+                    "Usage: {name} [--edge] [--cfg <name>] <path-to-rust-files>"
+                )
+            }
+            Self::ErrorProcessing(path, error) => {
+                let path = path.display();
+                write!(f, "Error processing {path}: {error}")
+            }
+            Self::InvalidPath(path) => {
+                let path = path.display();
+                write!(f, "Invalid path: {path}")
+            }
+            Self::InvalidCfgName(name) => {
+                write!(f, "Invalid cfg name '{name}': must be a valid Rust identifier")
+            }
+        }
+    }
+}
+
+impl Error for InstrumentError {}
+
+/// Visitor that instruments enum assignments
+pub struct EnumInstrumenter {
+    // Track enum types we've seen
+    enum_types: std::collections::HashSet<String>,
+    // Track enum variants and assign them unique IDs
+    enum_variants: std::collections::HashMap<String, u32>,
+    // Track if we're in a const context
+    in_const_context: bool,
+    // File currently being visited, used to key location IDs and to
+    // populate the manifest
+    current_file: PathBuf,
+    // Every instrumentation site emitted so far, across all files
+    locations: Vec<LocationRecord>,
+    // Emit `instrument_edge` (AFL-style transition coverage) instead of
+    // plain `instrument` (isolated state hits)
+    edge_mode: bool,
+    // cfg flag every injected call is gated behind, so an instrumented
+    // tree still compiles without the fuzzer runtime linked. Kept as an
+    // already-validated `Ident` so generating the call can't panic.
+    cfg_name: syn::Ident,
+}
+
+impl EnumInstrumenter {
+    pub fn new() -> Self {
+        Self {
+            enum_types: std::collections::HashSet::new(),
+            enum_variants: std::collections::HashMap::new(),
+            in_const_context: false,
+            current_file: PathBuf::new(),
+            locations: Vec::new(),
+            edge_mode: false,
+            cfg_name: format_ident!("{}", DEFAULT_CFG_NAME),
+        }
+    }
+
+    /// Emit `sginstrument::instrument_edge` calls instead of
+    /// `sginstrument::instrument`, so the fuzzer sees state transitions
+    /// rather than isolated state hits.
+    pub fn set_edge_mode(&mut self, edge_mode: bool) {
+        self.edge_mode = edge_mode;
+    }
+
+    /// Gate every injected call behind `#[cfg(#cfg_name)]` instead of the
+    /// default `#[cfg(fuzzing)]`. Fails if `cfg_name` isn't a valid ident.
+    ///
+    /// A non-default name needs its own `check-cfg` entry in the consuming
+    /// crate's `Cargo.toml`, or `-D warnings` builds will reject it as an
+    /// `unexpected_cfgs`.
+    pub fn set_cfg_name(&mut self, cfg_name: impl AsRef<str>) -> Result<(), InstrumentError> {
+        let cfg_name = cfg_name.as_ref();
+        self.cfg_name = syn::parse_str(cfg_name)
+            .map_err(|_| InstrumentError::InvalidCfgName(cfg_name.to_string()))?;
+        Ok(())
+    }
+
+    /// Generate the instrumentation call (only if not in const context),
+    /// gated behind `#[cfg(#cfg_name)]`.
+    fn create_instrumentation_call(
+        &mut self,
+        enum_name: &str,
+        variant_name: &str,
+        span: Span,
+    ) -> Option<Stmt> {
+        if self.in_const_context {
+            return None; // Skip instrumentation in const contexts
+        }
+
+        let location = location_id(&self.current_file, span, enum_name, variant_name);
+
+        // Create a unique key for this enum variant
+        let variant_key = format!("{enum_name}::{variant_name}");
+
+        // Assign a unique state value if we haven't seen this variant before
+        let next_state_value = self.enum_variants.len() as u32;
+        let state_value = *self
+            .enum_variants
+            .entry(variant_key)
+            .or_insert(next_state_value);
+
+        let start = span.start();
+        let end = span.end();
+        self.locations.push(LocationRecord {
+            location,
+            state_value,
+            file: self.current_file.clone(),
+            enum_name: enum_name.to_string(),
+            variant_name: variant_name.to_string(),
+            start_line: start.line,
+            start_column: start.column,
+            end_line: end.line,
+            end_column: end.column,
+        });
+
+        let cfg_ident = &self.cfg_name;
+        let call = if self.edge_mode {
+            parse_quote! {
+                #[cfg(#cfg_ident)]
+                sginstrument::instrument_edge(#location, #state_value);
+            }
+        } else {
+            parse_quote! {
+                #[cfg(#cfg_ident)]
+                sginstrument::instrument(#location, #state_value);
+            }
+        };
+        Some(call)
+    }
+
+    /// Serialize everything recorded so far into the manifest JSON format.
+    fn manifest_json(&self) -> String {
+        let mut locations_json = Vec::with_capacity(self.locations.len());
+        for record in &self.locations {
+            locations_json.push(format!(
+                concat!(
+                    "    {{\n",
+                    "      \"location\": {},\n",
+                    "      \"state_value\": {},\n",
+                    "      \"file\": \"{}\",\n",
+                    "      \"enum\": \"{}\",\n",
+                    "      \"variant\": \"{}\",\n",
+                    "      \"start\": {{ \"line\": {}, \"column\": {} }},\n",
+                    "      \"end\": {{ \"line\": {}, \"column\": {} }}\n",
+                    "    }}"
+                ),
+                record.location,
+                record.state_value,
+                json_escape(&record.file.display().to_string()),
+                json_escape(&record.enum_name),
+                json_escape(&record.variant_name),
+                record.start_line,
+                record.start_column,
+                record.end_line,
+                record.end_column,
+            ));
+        }
+
+        let mut variants_json = Vec::with_capacity(self.enum_variants.len());
+        for (variant_key, state_value) in &self.enum_variants {
+            variants_json.push(format!(
+                "    \"{}\": {state_value}",
+                json_escape(variant_key)
+            ));
+        }
+        variants_json.sort();
+
+        format!(
+            "{{\n  \"locations\": [\n{}\n  ],\n  \"enum_variants\": {{\n{}\n  }}\n}}\n",
+            locations_json.join(",\n"),
+            variants_json.join(",\n")
+        )
+    }
+
+    /// Write the manifest to `manifest_path`.
+    pub fn write_manifest(&self, manifest_path: &StdPath) -> Result<(), Box<dyn Error>> {
+        fs::write(manifest_path, self.manifest_json())?;
+        Ok(())
+    }
+
+    /// Extract enum type and variant from a path expression
+    fn extract_enum_info(&self, path: &Path) -> Option<(String, String)> {
+        if path.segments.len() >= 2 {
+            let enum_type = path.segments[path.segments.len() - 2].ident.to_string();
+            let variant = path.segments.last()?.ident.to_string();
+
+            // Only instrument if we know this is an enum type
+            if self.enum_types.contains(&enum_type) {
+                return Some((enum_type, variant));
+            }
+        }
+        None
+    }
+
+    /// Extract enum type and variant from a pattern that names a known enum
+    /// variant, as seen in a `match` arm, `if let`, or `while let`.
+    fn extract_enum_info_from_pat(&self, pat: &Pat) -> Option<(String, String, Span)> {
+        let path = match pat {
+            Pat::TupleStruct(pat_tuple_struct) => &pat_tuple_struct.path,
+            Pat::Struct(pat_struct) => &pat_struct.path,
+            Pat::Path(pat_path) => &pat_path.path,
+            _ => return None,
+        };
+
+        let (enum_name, variant_name) = self.extract_enum_info(path)?;
+        Some((enum_name, variant_name, path.span()))
+    }
+}
+
+impl Default for EnumInstrumenter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VisitMut for EnumInstrumenter {
+    /// Collect enum definitions
+    fn visit_item_enum_mut(&mut self, node: &mut ItemEnum) {
+        self.enum_types.insert(node.ident.to_string());
+        syn::visit_mut::visit_item_enum_mut(self, node);
+    }
+
+    /// Track const functions
+    fn visit_item_fn_mut(&mut self, node: &mut syn::ItemFn) {
+        let was_const = self.in_const_context;
+        if node.sig.constness.is_some() {
+            self.in_const_context = true;
+        }
+
+        syn::visit_mut::visit_item_fn_mut(self, node);
+
+        self.in_const_context = was_const;
+    }
+
+    /// Track const items
+    fn visit_item_const_mut(&mut self, node: &mut syn::ItemConst) {
+        let was_const = self.in_const_context;
+        self.in_const_context = true;
+
+        syn::visit_mut::visit_item_const_mut(self, node);
+
+        self.in_const_context = was_const;
+    }
+
+    /// Track static items
+    fn visit_item_static_mut(&mut self, node: &mut syn::ItemStatic) {
+        let was_const = self.in_const_context;
+        self.in_const_context = true;
+
+        syn::visit_mut::visit_item_static_mut(self, node);
+
+        self.in_const_context = was_const;
+    }
+
+    /// Instrument assignments in blocks
+    fn visit_block_mut(&mut self, node: &mut Block) {
+        let mut new_stmts = Vec::new();
+
+        for stmt in &node.stmts {
+            match stmt {
+                // Handle let bindings with enum values
+                Stmt::Local(local) => {
+                    if let Some(init) = &local.init
+                        && let Expr::Path(expr_path) = &*init.expr
+                        && let Some((enum_name, variant_name)) =
+                            self.extract_enum_info(&expr_path.path)
+                    {
+                        // Add instrumentation before the assignment
+                        if let Some(instrumentation) = self.create_instrumentation_call(
+                            &enum_name,
+                            &variant_name,
+                            expr_path.path.span(),
+                        ) {
+                            new_stmts.push(instrumentation);
+                        }
+                    }
+                    new_stmts.push(stmt.clone());
+                }
+
+                // Handle expression statements that might be assignments
+                Stmt::Expr(expr, semi) => match expr {
+                    Expr::Assign(assign) => {
+                        if let Expr::Path(expr_path) = &*assign.right
+                            && let Some((enum_name, variant_name)) =
+                                self.extract_enum_info(&expr_path.path)
+                            && let Some(instrumentation) = self.create_instrumentation_call(
+                                &enum_name,
+                                &variant_name,
+                                expr_path.path.span(),
+                            )
+                        {
+                            new_stmts.push(instrumentation);
+                        }
+
+                        new_stmts.push(Stmt::Expr(expr.clone(), *semi));
+                    }
+                    _ => new_stmts.push(stmt.clone()),
+                },
+
+                _ => new_stmts.push(stmt.clone()),
+            }
+        }
+
+        node.stmts = new_stmts;
+        syn::visit_mut::visit_block_mut(self, node);
+    }
+
+    /// Instrument function call arguments
+    fn visit_expr_call_mut(&mut self, node: &mut ExprCall) {
+        // Check if any arguments are enum variants
+        for arg in &mut node.args {
+            if let Expr::Path(expr_path) = arg &&
+                let Some((enum_name, variant_name)) = self.extract_enum_info(&expr_path.path) &&
+                    // For function arguments, we need a different approach
+                    // We could wrap the argument in a block expression
+                    let Some(instrumentation) = self.create_instrumentation_call(
+                        &enum_name,
+                        &variant_name,
+                        expr_path.path.span(),
+                    )
+            {
+                let original_arg = arg.clone();
+
+                *arg = parse_quote! {
+                    {
+                        #instrumentation
+                        #original_arg
+                    }
+                };
+            }
+        }
+        syn::visit_mut::visit_expr_call_mut(self, node);
+    }
+
+    /// Instrument enum variant construction: `Enum::Variant(..)` (parsed as
+    /// a call with a variant callee) and `Enum::Variant { .. }` (a struct
+    /// literal). Wrapping the *construction itself* in a block, rather
+    /// than one of its arguments, needs access to the surrounding `Expr`
+    /// slot, which `visit_expr_call_mut`/`visit_expr_struct_mut` don't have
+    /// (they only see their own node type) - so the swap happens here,
+    /// before dispatching to the default per-kind visitors.
+    fn visit_expr_mut(&mut self, node: &mut Expr) {
+        match node {
+            Expr::Call(expr_call) => {
+                if let Expr::Path(expr_path) = &*expr_call.func
+                    && let Some((enum_name, variant_name)) =
+                        self.extract_enum_info(&expr_path.path)
+                {
+                    let span = expr_path.path.span();
+                    // Instrument any enum-variant arguments first.
+                    self.visit_expr_call_mut(expr_call);
+
+                    if let Some(instrumentation) =
+                        self.create_instrumentation_call(&enum_name, &variant_name, span)
+                    {
+                        let original_call = node.clone();
+                        *node = parse_quote! {
+                            {
+                                #instrumentation
+                                #original_call
+                            }
+                        };
+                    }
+                    return;
+                }
+            }
+            Expr::Struct(expr_struct) => {
+                if let Some((enum_name, variant_name)) =
+                    self.extract_enum_info(&expr_struct.path)
+                {
+                    let span = expr_struct.path.span();
+                    self.visit_expr_struct_mut(expr_struct);
+
+                    if let Some(instrumentation) =
+                        self.create_instrumentation_call(&enum_name, &variant_name, span)
+                    {
+                        let original_struct = node.clone();
+                        *node = parse_quote! {
+                            {
+                                #instrumentation
+                                #original_struct
+                            }
+                        };
+                    }
+                    return;
+                }
+            }
+            _ => {}
+        }
+        syn::visit_mut::visit_expr_mut(self, node);
+    }
+
+    /// Recurse into a struct-like variant literal's field values; the
+    /// wrapping instrumentation is applied by `visit_expr_mut`, which is
+    /// the only place that can swap the node for a block.
+    fn visit_expr_struct_mut(&mut self, node: &mut syn::ExprStruct) {
+        syn::visit_mut::visit_expr_struct_mut(self, node);
+    }
+
+    /// Instrument `match` arms whose pattern names a known enum variant, so
+    /// the fuzzer sees which branch of a state machine was taken.
+    fn visit_expr_match_mut(&mut self, node: &mut ExprMatch) {
+        for arm in &mut node.arms {
+            if let Some((enum_name, variant_name, span)) =
+                self.extract_enum_info_from_pat(&arm.pat)
+                && let Some(instrumentation) =
+                    self.create_instrumentation_call(&enum_name, &variant_name, span)
+            {
+                let original_body = arm.body.clone();
+                *arm.body = parse_quote! {
+                    {
+                        #instrumentation
+                        #original_body
+                    }
+                };
+            }
+        }
+        syn::visit_mut::visit_expr_match_mut(self, node);
+    }
+
+    /// Instrument `if let Enum::Variant(..) = ...` the same way as a
+    /// `match` arm, by inserting the call at the top of the `then` branch.
+    fn visit_expr_if_mut(&mut self, node: &mut ExprIf) {
+        if let Expr::Let(expr_let) = &*node.cond
+            && let Some((enum_name, variant_name, span)) =
+                self.extract_enum_info_from_pat(&expr_let.pat)
+            && let Some(instrumentation) =
+                self.create_instrumentation_call(&enum_name, &variant_name, span)
+        {
+            node.then_branch.stmts.insert(0, instrumentation);
+        }
+        syn::visit_mut::visit_expr_if_mut(self, node);
+    }
+
+    /// Instrument `while let Enum::Variant(..) = ...` the same way as
+    /// `if let`, by inserting the call at the top of the loop body.
+    fn visit_expr_while_mut(&mut self, node: &mut ExprWhile) {
+        if let Expr::Let(expr_let) = &*node.cond
+            && let Some((enum_name, variant_name, span)) =
+                self.extract_enum_info_from_pat(&expr_let.pat)
+            && let Some(instrumentation) =
+                self.create_instrumentation_call(&enum_name, &variant_name, span)
+        {
+            node.body.stmts.insert(0, instrumentation);
+        }
+        syn::visit_mut::visit_expr_while_mut(self, node);
+    }
+}
+
+/// Process a single Rust file
+pub fn process_file(
+    instrumenter: &mut EnumInstrumenter,
+    file_path: &StdPath,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(file_path)?;
+    let mut syntax_tree = syn::parse_file(&content)?;
+
+    // Apply instrumentation
+    instrumenter.current_file = file_path.to_owned();
+    instrumenter.visit_file_mut(&mut syntax_tree);
+
+    // Write back the modified code
+    let output = syntax_tree.to_token_stream().to_string();
+
+    // Write back to the same file (overwrite)
+    fs::write(file_path, output)?;
+
+    println!("Processed: {}", file_path.display());
+    Ok(())
+}
+
+/// Process all Rust files in a directory, overwriting them in place.
+pub fn process_directory(
+    instrumenter: &mut EnumInstrumenter,
+    dir_path: &StdPath,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in WalkDir::new(dir_path) {
+        let entry = entry?;
+        if entry.file_type().is_file()
+            && let Some(extension) = entry.path().extension()
+            && extension == "rs"
+            && let Err(e) = process_file(instrumenter, entry.path())
+        {
+            return Err(InstrumentError::ErrorProcessing(entry.path().to_owned(), e).into());
+        }
+    }
+    Ok(())
+}
+
+/// Configuration for [`instrument_tree_with_options`]: the edge-mode and
+/// cfg-name knobs the CLI exposes via `--edge`/`--cfg`, for `build.rs`
+/// callers that need the same control.
+#[derive(Debug, Clone)]
+pub struct InstrumentOptions {
+    pub edge_mode: bool,
+    /// See [`EnumInstrumenter::set_cfg_name`] for the `check-cfg` caveat
+    /// when this isn't the default.
+    pub cfg_name: String,
+}
+
+impl Default for InstrumentOptions {
+    fn default() -> Self {
+        Self {
+            edge_mode: false,
+            cfg_name: DEFAULT_CFG_NAME.to_string(),
+        }
+    }
+}
+
+/// Instrument every `.rs` file under `src_dir` and write the instrumented
+/// copies under `out_dir`, mirroring the source tree's relative layout.
+/// Unlike [`process_directory`], the originals under `src_dir` are left
+/// untouched: this is meant to be called from a consumer's `build.rs`,
+/// which then compiles or `include!`s the generated copies from `OUT_DIR`
+/// under a fuzzing profile, instead of instrumenting the checkout itself.
+///
+/// A `sginstrument-manifest.json` mapping every emitted location ID back
+/// to its source is written to `out_dir` alongside the instrumented files.
+/// `cargo:rerun-if-changed` lines are printed for every input file so
+/// Cargo only re-instruments when a source file actually changes.
+///
+/// Uses state-machine coverage with the default cfg name; see
+/// [`instrument_tree_with_options`] for edge-mode coverage or a custom cfg
+/// name.
+pub fn instrument_tree(
+    src_dir: &StdPath,
+    out_dir: &StdPath,
+) -> Result<(), Box<dyn std::error::Error>> {
+    instrument_tree_with_options(src_dir, out_dir, InstrumentOptions::default())
+}
+
+/// Like [`instrument_tree`], but lets a `build.rs` caller opt into
+/// AFL-style edge coverage or gate the injected calls behind a custom cfg
+/// name, mirroring the CLI's `--edge`/`--cfg` flags.
+pub fn instrument_tree_with_options(
+    src_dir: &StdPath,
+    out_dir: &StdPath,
+    options: InstrumentOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut instrumenter = EnumInstrumenter::new();
+    instrumenter.set_edge_mode(options.edge_mode);
+    instrumenter.set_cfg_name(options.cfg_name)?;
+
+    for entry in WalkDir::new(src_dir) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Some(extension) = entry.path().extension() else {
+            continue;
+        };
+        if extension != "rs" {
+            continue;
+        }
+
+        let src_path = entry.path();
+        println!("cargo:rerun-if-changed={}", src_path.display());
+
+        let relative = src_path.strip_prefix(src_dir).unwrap_or(src_path);
+        let out_path = out_dir.join(relative);
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = fs::read_to_string(src_path)?;
+        let mut syntax_tree = syn::parse_file(&content)
+            .map_err(|e| InstrumentError::ErrorProcessing(src_path.to_owned(), e.into()))?;
+
+        instrumenter.current_file = src_path.to_owned();
+        instrumenter.visit_file_mut(&mut syntax_tree);
+
+        fs::write(&out_path, syntax_tree.to_token_stream().to_string())?;
+    }
+
+    instrumenter.write_manifest(&out_dir.join(MANIFEST_FILE_NAME))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enum_instrumentation() {
+        let input = r#"
+enum Status {
+    Active,
+    Inactive,
+    Pending(i32),
+}
+
+fn main() {
+    let status = Status::Active;
+    let mut other = Status::Pending(42);
+    other = Status::Inactive;
+
+    process_status(Status::Active);
+}
+"#;
+
+        let mut syntax_tree = syn::parse_file(input).unwrap();
+        let mut instrumenter = EnumInstrumenter::new();
+        instrumenter.current_file = PathBuf::from("src/example.rs");
+        instrumenter.visit_file_mut(&mut syntax_tree);
+
+        let output = syntax_tree.to_token_stream().to_string();
+
+        // Verify that instrumentation calls were added
+        assert!(output.contains("sginstrument :: instrument ("));
+        assert_eq!(instrumenter.enum_variants.len(), 3);
+        assert_eq!(instrumenter.locations.len(), 4);
+        println!("Instrumented code:\n{}", output);
+    }
+
+    #[test]
+    fn test_location_ids_are_deterministic() {
+        let input = r#"
+enum Status {
+    Active,
+    Inactive,
+}
+
+fn main() {
+    let status = Status::Active;
+}
+"#;
+
+        let run = || {
+            let mut syntax_tree = syn::parse_file(input).unwrap();
+            let mut instrumenter = EnumInstrumenter::new();
+            instrumenter.current_file = PathBuf::from("src/example.rs");
+            instrumenter.visit_file_mut(&mut syntax_tree);
+            instrumenter.locations
+        };
+
+        // Location IDs are keyed on file + span, not visitation order, so
+        // repeated runs over the same input must agree.
+        let first = run();
+        let second = run();
+        assert_eq!(first[0].location, second[0].location);
+    }
+
+    #[test]
+    fn test_match_if_let_while_let_instrumentation() {
+        let input = r#"
+enum Status {
+    Active,
+    Inactive,
+    Pending(i32),
+}
+
+fn main() {
+    let status = Status::Active;
+
+    match status {
+        Status::Active => {}
+        Status::Inactive => {}
+        Status::Pending(n) => {}
+    }
+
+    if let Status::Active = status {}
+
+    while let Status::Pending(n) = status {
+        break;
+    }
+}
+"#;
+
+        let mut syntax_tree = syn::parse_file(input).unwrap();
+        let mut instrumenter = EnumInstrumenter::new();
+        instrumenter.current_file = PathBuf::from("src/example.rs");
+        instrumenter.visit_file_mut(&mut syntax_tree);
+
+        let output = syntax_tree.to_token_stream().to_string();
+
+        // One location per match arm, one for the if-let, one for the while-let,
+        // plus the initial `let status = Status::Active` assignment.
+        assert_eq!(instrumenter.locations.len(), 6);
+        println!("Instrumented code:\n{}", output);
+    }
+
+    #[test]
+    fn test_tuple_and_struct_variant_construction_instrumentation() {
+        let input = r#"
+enum Status {
+    Active,
+    Pending(i32),
+    Errored { code: i32 },
+}
+
+fn main() {
+    let a = Status::Pending(42);
+    let b = Status::Errored { code: 1 };
+    process_status(Status::Pending(7));
+}
+"#;
+
+        let mut syntax_tree = syn::parse_file(input).unwrap();
+        let mut instrumenter = EnumInstrumenter::new();
+        instrumenter.current_file = PathBuf::from("src/example.rs");
+        instrumenter.visit_file_mut(&mut syntax_tree);
+
+        let output = syntax_tree.to_token_stream().to_string();
+
+        // One site each for the two `let` constructions and the call argument.
+        assert_eq!(instrumenter.locations.len(), 3);
+        assert!(output.contains("sginstrument :: instrument ("));
+        println!("Instrumented code:\n{}", output);
+    }
+
+    #[test]
+    fn test_edge_mode_emits_instrument_edge() {
+        let input = r#"
+enum Status {
+    Active,
+    Inactive,
+}
+
+fn main() {
+    let status = Status::Active;
+}
+"#;
+
+        let mut syntax_tree = syn::parse_file(input).unwrap();
+        let mut instrumenter = EnumInstrumenter::new();
+        instrumenter.current_file = PathBuf::from("src/example.rs");
+        instrumenter.set_edge_mode(true);
+        instrumenter.visit_file_mut(&mut syntax_tree);
+
+        let output = syntax_tree.to_token_stream().to_string();
+
+        assert!(output.contains("sginstrument :: instrument_edge ("));
+        assert!(!output.contains("sginstrument :: instrument ("));
+    }
+
+    #[test]
+    fn test_injected_calls_are_cfg_gated() {
+        let input = r#"
+enum Status {
+    Active,
+}
+
+fn main() {
+    let status = Status::Active;
+}
+"#;
+
+        let mut syntax_tree = syn::parse_file(input).unwrap();
+        let mut instrumenter = EnumInstrumenter::new();
+        instrumenter.current_file = PathBuf::from("src/example.rs");
+        instrumenter.visit_file_mut(&mut syntax_tree);
+
+        let output = syntax_tree.to_token_stream().to_string();
+        assert!(output.contains("# [cfg (fuzzing)]"));
+    }
+
+    #[test]
+    fn test_custom_cfg_name() {
+        let input = r#"
+enum Status {
+    Active,
+}
+
+fn main() {
+    let status = Status::Active;
+}
+"#;
+
+        let mut syntax_tree = syn::parse_file(input).unwrap();
+        let mut instrumenter = EnumInstrumenter::new();
+        instrumenter.current_file = PathBuf::from("src/example.rs");
+        instrumenter.set_cfg_name("my_fuzz_cfg").unwrap();
+        instrumenter.visit_file_mut(&mut syntax_tree);
+
+        let output = syntax_tree.to_token_stream().to_string();
+        assert!(output.contains("# [cfg (my_fuzz_cfg)]"));
+    }
+
+    #[test]
+    fn test_invalid_cfg_name_is_rejected_not_panicked() {
+        let mut instrumenter = EnumInstrumenter::new();
+        assert!(matches!(
+            instrumenter.set_cfg_name("my-bad-cfg"),
+            Err(InstrumentError::InvalidCfgName(name)) if name == "my-bad-cfg"
+        ));
+    }
+
+    #[test]
+    fn test_instrument_tree_leaves_originals_untouched() {
+        let src_dir = std::env::temp_dir().join(format!(
+            "sginstrument-test-src-{}",
+            std::process::id()
+        ));
+        let out_dir = std::env::temp_dir().join(format!(
+            "sginstrument-test-out-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&src_dir);
+        let _ = fs::remove_dir_all(&out_dir);
+        fs::create_dir_all(&src_dir).unwrap();
+
+        let original = "enum Status { Active }\nfn main() { let s = Status::Active; }\n";
+        let src_file = src_dir.join("lib.rs");
+        fs::write(&src_file, original).unwrap();
+
+        instrument_tree(&src_dir, &out_dir).unwrap();
+
+        assert_eq!(fs::read_to_string(&src_file).unwrap(), original);
+        let instrumented = fs::read_to_string(out_dir.join("lib.rs")).unwrap();
+        assert!(instrumented.contains("sginstrument :: instrument ("));
+        assert!(out_dir.join(MANIFEST_FILE_NAME).exists());
+
+        fs::remove_dir_all(&src_dir).unwrap();
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_instrument_tree_with_options_honours_edge_mode_and_cfg_name() {
+        let src_dir = std::env::temp_dir().join(format!(
+            "sginstrument-test-opts-src-{}",
+            std::process::id()
+        ));
+        let out_dir = std::env::temp_dir().join(format!(
+            "sginstrument-test-opts-out-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&src_dir);
+        let _ = fs::remove_dir_all(&out_dir);
+        fs::create_dir_all(&src_dir).unwrap();
+
+        let original = "enum Status { Active }\nfn main() { let s = Status::Active; }\n";
+        let src_file = src_dir.join("lib.rs");
+        fs::write(&src_file, original).unwrap();
+
+        instrument_tree_with_options(
+            &src_dir,
+            &out_dir,
+            InstrumentOptions {
+                edge_mode: true,
+                cfg_name: "my_fuzz_cfg".to_string(),
+            },
+        )
+        .unwrap();
+
+        let instrumented = fs::read_to_string(out_dir.join("lib.rs")).unwrap();
+        assert!(instrumented.contains("sginstrument :: instrument_edge ("));
+        assert!(instrumented.contains("# [cfg (my_fuzz_cfg)]"));
+
+        fs::remove_dir_all(&src_dir).unwrap();
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
@@ -1,20 +1,86 @@
+//! Instrumentation calls injected by the instrumenter are gated behind `#[cfg(fuzzing)]` (or a
+//! custom name set via [`instrumenter::EnumInstrumenter::set_cfg_name`] /
+//! [`InstrumentOptions::cfg_name`]). Consumers building with
+//! `-D warnings` must register that cfg in their own `Cargo.toml`:
+//! `[lints.rust] unexpected_cfgs = { level = "warn", check-cfg =
+//! ["cfg(fuzzing)"] }`, otherwise rustc's `unexpected_cfgs` lint rejects it.
+
+pub mod instrumenter;
+
+pub use instrumenter::{InstrumentOptions, instrument_tree, instrument_tree_with_options};
+
+#[cfg(fuzzing)]
+use std::cell::Cell;
+
+#[cfg(fuzzing)]
 unsafe extern "C" {
     fn __sfuzzer_instrument(location: std::os::raw::c_uint, state_value: std::os::raw::c_uint);
 }
 
-/// Informs the fuzzer that a new state has been reached.
+#[cfg(fuzzing)]
+thread_local! {
+    /// The mixed `current` value from the previous `instrument_edge` call on
+    /// this thread, right-shifted by one. Used to turn isolated state hits
+    /// into AFL-style edges.
+    static PREV: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Informs the fuzzer that a new state has been reached. No-op without
+/// `--cfg fuzzing`.
+#[cfg(fuzzing)]
 pub fn instrument(location: u32, state_value: u32) {
     unsafe {
         __sfuzzer_instrument(location, state_value);
     }
 }
 
-#[cfg(test)]
+#[cfg(not(fuzzing))]
+pub fn instrument(_location: u32, _state_value: u32) {}
+
+/// Like [`instrument`], but reports the transition into this state (mixed
+/// with the previous call's state) rather than the state in isolation, so
+/// `A -> B` and `B -> A` are seen as distinct edges. No-op without
+/// `--cfg fuzzing`.
+#[cfg(fuzzing)]
+pub fn instrument_edge(location: u32, state_value: u32) {
+    let current = location.wrapping_mul(0x9e3779b1) ^ state_value;
+    let edge = PREV.with(|prev| {
+        let edge = current ^ prev.get();
+        prev.set(current >> 1);
+        edge
+    });
+
+    unsafe {
+        __sfuzzer_instrument(edge, state_value);
+    }
+}
+
+#[cfg(not(fuzzing))]
+pub fn instrument_edge(_location: u32, _state_value: u32) {}
+
+#[cfg(all(test, fuzzing))]
 mod test {
-    use crate::instrument;
+    use crate::{instrument, instrument_edge};
 
     #[test]
     fn test() {
         instrument(0, 0);
     }
+
+    #[test]
+    fn test_edge() {
+        instrument_edge(1, 0);
+        instrument_edge(1, 1);
+    }
+}
+
+#[cfg(all(test, not(fuzzing)))]
+mod test_no_fuzzing {
+    use crate::{instrument, instrument_edge};
+
+    #[test]
+    fn test_no_op_without_fuzzing_cfg() {
+        instrument(0, 0);
+        instrument_edge(1, 0);
+    }
 }